@@ -22,10 +22,17 @@ use solana_program::alt_bn128::{
     prelude::*,
     compression::prelude::*,
 };
+use solana_program::keccak;
+
+use crate::merkle::{IncrementalMerkleTree, TreeHasher};
 
 /// Groth16 proof size in bytes
 pub const PROOF_SIZE: usize = 256;
 
+/// Compressed Groth16 proof size in bytes: compressed G1 (32) + compressed
+/// G2 (64) + compressed G1 (32). Half of `PROOF_SIZE`.
+pub const COMPRESSED_PROOF_SIZE: usize = 128;
+
 /// Size of a single public input (field element)
 pub const PUBLIC_INPUT_SIZE: usize = 32;
 
@@ -129,6 +136,394 @@ fn is_vk_initialized() -> bool {
     vk::ALPHA_G1.iter().any(|&b| b != 0)
 }
 
+/// On-chain verifying key, stored in its own account so a circuit change
+/// (a new `TREE_DEPTH`, an added public input, a different circuit
+/// altogether) can be rolled out via a governed account write instead of a
+/// full program redeploy. Same layout as `mod vk`, except `ic` is a `Vec`
+/// since the number of public inputs is no longer fixed at compile time.
+#[account]
+#[derive(Debug)]
+pub struct VerifyingKey {
+    /// Alpha * G1 (64 bytes)
+    pub alpha_g1: [u8; 64],
+    /// Beta * G2 (128 bytes)
+    pub beta_g2: [u8; 128],
+    /// Gamma * G2 (128 bytes)
+    pub gamma_g2: [u8; 128],
+    /// Delta * G2 (128 bytes)
+    pub delta_g2: [u8; 128],
+    /// IC elements: one for capacity plus one per public input.
+    pub ic: Vec<[u8; 64]>,
+}
+
+impl VerifyingKey {
+    /// Account space for the fixed-size fields, including the 8-byte Anchor
+    /// discriminator. `ic` is variable-length, so callers size the account
+    /// with [`VerifyingKey::space_for`] instead of a single `SIZE` constant.
+    pub const FIXED_SIZE: usize = 8 // discriminator
+        + 64 // alpha_g1
+        + 128 // beta_g2
+        + 128 // gamma_g2
+        + 128; // delta_g2
+
+    /// Total account space needed to hold `num_ic` IC points (Borsh's
+    /// `Vec` length prefix is 4 bytes).
+    pub fn space_for(num_ic: usize) -> usize {
+        Self::FIXED_SIZE + 4 + num_ic * 64
+    }
+}
+
+/// Check whether a `VerifyingKey` account has actually been populated.
+/// Unlike the compiled-in `mod vk` constants, an account's mere existence
+/// isn't proof of initialization: Anchor zero-initializes account data on
+/// creation, so a freshly-allocated account's `alpha_g1` (and `ic`) are
+/// still all-zero / empty until a governed update writes the real key.
+pub fn is_vk_account_initialized(vk: &VerifyingKey) -> bool {
+    vk.alpha_g1.iter().any(|&b| b != 0) && !vk.ic.is_empty()
+}
+
+/// Verify a Groth16 proof against an arbitrary number of public inputs and an
+/// on-chain [`VerifyingKey`] account. This is the reusable core: any circuit
+/// (range proofs, membership proofs, a withdraw circuit with more inputs)
+/// can call it as long as its `VerifyingKey` has `public_inputs.len() + 1`
+/// IC points. `verify_groth16_withdraw` and `verify_groth16_withdraw_with_vk`
+/// are thin wrappers fixing the withdraw circuit's four inputs.
+///
+/// Groth16 verification equation:
+/// e(A, B) = e(alpha, beta) * e(L, gamma) * e(C, delta)
+///
+/// Rearranged for pairing check (product of pairings = 1):
+/// e(-A, B) * e(alpha, beta) * e(L, gamma) * e(C, delta) = 1
+///
+/// Where L = IC[0] + sum(public_input[i] * IC[i+1])
+pub fn verify_groth16(
+    proof_bytes: &[u8],
+    public_inputs: &[[u8; 32]],
+    vk: &VerifyingKey,
+) -> Result<bool> {
+    let proof = Groth16Proof::from_bytes(proof_bytes)
+        .ok_or(Groth16Error::InvalidProofSize)?;
+
+    verify_groth16_proof(&proof, public_inputs, vk)
+}
+
+/// Same check as [`verify_groth16`], but the proof arrives in its compressed
+/// 128-byte wire format (32-byte compressed G1 for A, 64-byte compressed G2
+/// for B, 32-byte compressed G1 for C) and is decompressed on-chain via the
+/// alt_bn128 compression syscalls first. Halves the payload a relayer has to
+/// submit per withdrawal, which matters given Solana's per-transaction size
+/// limits; the pairing check itself is unchanged.
+pub fn verify_groth16_compressed(
+    compressed_proof_bytes: &[u8],
+    public_inputs: &[[u8; 32]],
+    vk: &VerifyingKey,
+) -> Result<bool> {
+    let proof = Groth16Proof::from_compressed_bytes(compressed_proof_bytes)?;
+
+    verify_groth16_proof(&proof, public_inputs, vk)
+}
+
+/// Core Groth16 pairing check, shared by the uncompressed and compressed
+/// entry points once each has produced an uncompressed [`Groth16Proof`].
+fn verify_groth16_proof(
+    proof: &Groth16Proof,
+    public_inputs: &[[u8; 32]],
+    vk: &VerifyingKey,
+) -> Result<bool> {
+    require!(
+        is_vk_account_initialized(vk),
+        Groth16Error::VkNotInitialized
+    );
+    let l_point = compute_l_point(public_inputs, vk)?;
+
+    // Prepare pairing input: 4 pairs of (G1, G2) points
+    // Each pair is 192 bytes (64 G1 + 128 G2)
+    let mut pairing_input = [0u8; 768]; // 4 * 192
+
+    // Pair 1: (-A, B) - negate A for the pairing check
+    let neg_a = negate_g1(&proof.a);
+    pairing_input[0..64].copy_from_slice(&neg_a);
+    pairing_input[64..192].copy_from_slice(&proof.b);
+
+    // Pair 2: (alpha, beta)
+    pairing_input[192..256].copy_from_slice(&vk.alpha_g1);
+    pairing_input[256..384].copy_from_slice(&vk.beta_g2);
+
+    // Pair 3: (L, gamma)
+    pairing_input[384..448].copy_from_slice(&l_point);
+    pairing_input[448..576].copy_from_slice(&vk.gamma_g2);
+
+    // Pair 4: (C, delta)
+    pairing_input[576..640].copy_from_slice(&proof.c);
+    pairing_input[640..768].copy_from_slice(&vk.delta_g2);
+
+    // Perform pairing check. Returns true if product of pairings equals 1.
+    let pairing_result = alt_bn128_pairing(&pairing_input)
+        .map_err(|_| Groth16Error::PairingFailed)?;
+
+    Ok(pairing_result[31] == 1)
+}
+
+/// Compute `L = IC[0] + sum(public_input[i] * IC[i+1])` against `vk`. Shared
+/// by the single-proof pairing check and the batch verifier below, since
+/// both need the same per-proof linear combination of IC points.
+fn compute_l_point(public_inputs: &[[u8; 32]], vk: &VerifyingKey) -> Result<[u8; 64]> {
+    require!(
+        public_inputs.len() + 1 == vk.ic.len(),
+        Groth16Error::InvalidPublicInputs
+    );
+
+    let mut l_point = vk.ic[0];
+    for (i, input) in public_inputs.iter().enumerate() {
+        let scaled = scalar_mul_g1(&vk.ic[i + 1], input)?;
+        l_point = add_g1(&l_point, &scaled)?;
+    }
+
+    Ok(l_point)
+}
+
+/// Scalar-multiply a G1 point via the `alt_bn128_multiplication` syscall.
+fn scalar_mul_g1(point: &[u8; 64], scalar: &[u8; 32]) -> Result<[u8; 64]> {
+    let mut input = [0u8; 96]; // 64 bytes point + 32 bytes scalar
+    input[0..64].copy_from_slice(point);
+    input[64..96].copy_from_slice(scalar);
+
+    let result =
+        alt_bn128_multiplication(&input).map_err(|_| Groth16Error::ScalarMulFailed)?;
+
+    let mut out = [0u8; 64];
+    out.copy_from_slice(&result);
+    Ok(out)
+}
+
+/// Add two G1 points via the `alt_bn128_addition` syscall.
+fn add_g1(a: &[u8; 64], b: &[u8; 64]) -> Result<[u8; 64]> {
+    let mut input = [0u8; 128];
+    input[0..64].copy_from_slice(a);
+    input[64..128].copy_from_slice(b);
+
+    let result = alt_bn128_addition(&input).map_err(|_| Groth16Error::PointAddFailed)?;
+
+    let mut out = [0u8; 64];
+    out.copy_from_slice(&result);
+    Ok(out)
+}
+
+/// Verify a Groth16 proof for a withdrawal against an on-chain
+/// [`VerifyingKey`] account rather than the compiled-in `mod vk` constants.
+/// This is the rotation path: governance updates the account, and every
+/// subsequent call to this function verifies against the new key without a
+/// program redeploy. Thin wrapper over [`verify_groth16`] fixing the
+/// withdraw circuit's four public inputs.
+pub fn verify_groth16_withdraw_with_vk(
+    proof_bytes: &[u8],
+    root: &[u8; 32],
+    nullifier_hash: &[u8; 32],
+    recipient: &[u8; 32],
+    amount: &[u8; 32],
+    vk: &VerifyingKey,
+) -> Result<bool> {
+    verify_groth16(
+        proof_bytes,
+        &[*root, *nullifier_hash, *recipient, *amount],
+        vk,
+    )
+}
+
+/// Same check as [`verify_groth16_withdraw_with_vk`], but first confirms
+/// `root` is actually one the on-chain commitment tree has produced, via
+/// [`IncrementalMerkleTree::is_known_root`]. Nothing else binds a proof's
+/// `root` public input to the real deposit set: without this, a relayer
+/// could submit a proof whose `root` is a value that never came from the
+/// tree at all. Using `is_known_root` (rather than requiring an exact match
+/// on `tree.root()`) also means a proof built against a root that's since
+/// been superseded by another deposit still verifies, instead of failing
+/// whenever a deposit lands between proof generation and submission.
+pub fn verify_groth16_withdraw_with_tree<H: TreeHasher>(
+    proof_bytes: &[u8],
+    tree: &IncrementalMerkleTree<H>,
+    root: &[u8; 32],
+    nullifier_hash: &[u8; 32],
+    recipient: &[u8; 32],
+    amount: &[u8; 32],
+    vk: &VerifyingKey,
+) -> Result<bool> {
+    require!(tree.is_known_root(root), Groth16Error::UnknownRoot);
+
+    verify_groth16_withdraw_with_vk(proof_bytes, root, nullifier_hash, recipient, amount, vk)
+}
+
+/// BN254 scalar field order `r`, used to reduce the random linear
+/// combination scalars in [`verify_groth16_batch`] into the field the curve
+/// operations expect.
+const SCALAR_FIELD_R: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// Returns true if big-endian 256-bit `a >= b`.
+fn be_ge(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    for i in 0..32 {
+        if a[i] != b[i] {
+            return a[i] > b[i];
+        }
+    }
+    true
+}
+
+/// `a -= b` in place, assuming big-endian 256-bit `a >= b`.
+fn be_sub_assign(a: &mut [u8; 32], b: &[u8; 32]) {
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let diff = a[i] as i16 - b[i] as i16 - borrow;
+        if diff < 0 {
+            a[i] = (diff + 256) as u8;
+            borrow = 1;
+        } else {
+            a[i] = diff as u8;
+            borrow = 0;
+        }
+    }
+}
+
+/// `a + b`, assuming both are already reduced mod `SCALAR_FIELD_R` (so the
+/// sum is below `2 * r < 2^256` and a plain 256-bit addition can't overflow).
+fn be_add(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    let mut carry = 0u16;
+    for i in (0..32).rev() {
+        let sum = a[i] as u16 + b[i] as u16 + carry;
+        result[i] = (sum & 0xFF) as u8;
+        carry = sum >> 8;
+    }
+    result
+}
+
+/// Reduce an arbitrary 256-bit big-endian value modulo the BN254 scalar
+/// field order `SCALAR_FIELD_R`, via schoolbook binary long division: shift
+/// each bit of `value` into a running remainder from the top, subtracting
+/// the modulus whenever the remainder reaches it.
+fn reduce_mod_scalar_field(value: &[u8; 32]) -> [u8; 32] {
+    let mut remainder = [0u8; 32];
+    for &byte in value.iter() {
+        for bit_index in (0..8).rev() {
+            let bit = (byte >> bit_index) & 1;
+
+            // remainder = (remainder << 1) | bit
+            let mut carry = bit;
+            for i in (0..32).rev() {
+                let next_carry = remainder[i] >> 7;
+                remainder[i] = (remainder[i] << 1) | carry;
+                carry = next_carry;
+            }
+
+            if be_ge(&remainder, &SCALAR_FIELD_R) {
+                be_sub_assign(&mut remainder, &SCALAR_FIELD_R);
+            }
+        }
+    }
+    remainder
+}
+
+/// `(a + b) mod SCALAR_FIELD_R`, assuming `a` and `b` are already reduced.
+fn add_mod_scalar_field(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut sum = be_add(a, b);
+    if be_ge(&sum, &SCALAR_FIELD_R) {
+        be_sub_assign(&mut sum, &SCALAR_FIELD_R);
+    }
+    sum
+}
+
+/// Derive one pseudo-random BN254 scalar per proof from a single transcript
+/// hash over every proof and public input in the batch, so a relayer can't
+/// predict or choose the scalars before the batch is assembled.
+fn derive_batch_scalars(proofs: &[Groth16Proof], inputs: &[WithdrawPublicInputs]) -> Vec<[u8; 32]> {
+    let mut transcript = Vec::with_capacity(proofs.len() * PROOF_SIZE + inputs.len() * 128);
+    for proof in proofs {
+        transcript.extend_from_slice(&proof.to_bytes());
+    }
+    for input in inputs {
+        transcript.extend_from_slice(&input.root);
+        transcript.extend_from_slice(&input.nullifier_hash);
+        transcript.extend_from_slice(&input.recipient);
+        transcript.extend_from_slice(&input.amount);
+    }
+    let transcript_hash = keccak::hash(&transcript).to_bytes();
+
+    (0..proofs.len())
+        .map(|i| {
+            let mut seed = transcript_hash.to_vec();
+            seed.extend_from_slice(&(i as u32).to_le_bytes());
+            reduce_mod_scalar_field(&keccak::hash(&seed).to_bytes())
+        })
+        .collect()
+}
+
+/// Verify a batch of withdrawal proofs with a single `alt_bn128_pairing`
+/// call instead of one pairing check (4 pairings) per proof.
+///
+/// For `n` proofs this costs `n + 3` pairings instead of `4n`: derive a
+/// pseudo-random scalar `r_i` per proof (see [`derive_batch_scalars`]), keep
+/// each proof's distinct `e(-r_i * A_i, B_i)` pair, and collapse the shared
+/// fixed-base terms — which are linear in their scaled G1 argument — into
+/// `e((sum r_i) * alpha, beta)`, `e(sum(r_i * L_i), gamma)`, and
+/// `e(sum(r_i * C_i), delta)`. Because every proof's `B_i` (and the shared
+/// `beta`/`gamma`/`delta`) differ, a forged proof only survives this check
+/// with probability roughly `n / r` (`r` the scalar field order) rather than
+/// certainly, so batching doesn't weaken soundness in any way that matters.
+pub fn verify_groth16_batch(
+    proofs: &[Groth16Proof],
+    inputs: &[WithdrawPublicInputs],
+    vk: &VerifyingKey,
+) -> Result<bool> {
+    require!(!proofs.is_empty(), Groth16Error::InvalidPublicInputs);
+    require!(proofs.len() == inputs.len(), Groth16Error::InvalidPublicInputs);
+    require!(
+        is_vk_account_initialized(vk),
+        Groth16Error::VkNotInitialized
+    );
+
+    let scalars = derive_batch_scalars(proofs, inputs);
+
+    let mut pairing_input = Vec::with_capacity((proofs.len() + 3) * 192);
+    let mut sum_r = [0u8; 32];
+    let mut sum_r_l = [0u8; 64]; // point at infinity; accumulates sum(r_i * L_i)
+    let mut sum_r_c = [0u8; 64]; // point at infinity; accumulates sum(r_i * C_i)
+
+    for ((proof, input), scalar) in proofs.iter().zip(inputs.iter()).zip(scalars.iter()) {
+        // This proof's distinct pair: e(-r_i * A_i, B_i).
+        let neg_a = negate_g1(&proof.a);
+        let scaled_neg_a = scalar_mul_g1(&neg_a, scalar)?;
+        pairing_input.extend_from_slice(&scaled_neg_a);
+        pairing_input.extend_from_slice(&proof.b);
+
+        // Accumulate the shared terms.
+        sum_r = add_mod_scalar_field(&sum_r, scalar);
+
+        let l_point = compute_l_point(&input.to_verifier_inputs(), vk)?;
+        let scaled_l = scalar_mul_g1(&l_point, scalar)?;
+        sum_r_l = add_g1(&sum_r_l, &scaled_l)?;
+
+        let scaled_c = scalar_mul_g1(&proof.c, scalar)?;
+        sum_r_c = add_g1(&sum_r_c, &scaled_c)?;
+    }
+
+    let scaled_alpha = scalar_mul_g1(&vk.alpha_g1, &sum_r)?;
+    pairing_input.extend_from_slice(&scaled_alpha);
+    pairing_input.extend_from_slice(&vk.beta_g2);
+
+    pairing_input.extend_from_slice(&sum_r_l);
+    pairing_input.extend_from_slice(&vk.gamma_g2);
+
+    pairing_input.extend_from_slice(&sum_r_c);
+    pairing_input.extend_from_slice(&vk.delta_g2);
+
+    let pairing_result =
+        alt_bn128_pairing(&pairing_input).map_err(|_| Groth16Error::PairingFailed)?;
+
+    Ok(pairing_result[31] == 1)
+}
+
 /// Groth16 proof structure
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct Groth16Proof {
@@ -166,6 +561,25 @@ impl Groth16Proof {
         bytes[192..256].copy_from_slice(&self.c);
         bytes
     }
+
+    /// Parse proof from its compressed 128-byte wire format (32-byte
+    /// compressed G1 for A, 64-byte compressed G2 for B, 32-byte compressed
+    /// G1 for C), decompressing each point on-chain via the alt_bn128
+    /// compression syscalls.
+    pub fn from_compressed_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < COMPRESSED_PROOF_SIZE {
+            return Err(Groth16Error::InvalidProofSize.into());
+        }
+
+        let a = alt_bn128_g1_decompress(&bytes[0..32])
+            .map_err(|_| Groth16Error::InvalidProofSize)?;
+        let b = alt_bn128_g2_decompress(&bytes[32..96])
+            .map_err(|_| Groth16Error::InvalidProofSize)?;
+        let c = alt_bn128_g1_decompress(&bytes[96..128])
+            .map_err(|_| Groth16Error::InvalidProofSize)?;
+
+        Ok(Self { a, b, c })
+    }
 }
 
 /// Public inputs for the withdrawal circuit
@@ -205,6 +619,8 @@ pub enum Groth16Error {
     ScalarMulFailed,
     #[msg("Point addition failed")]
     PointAddFailed,
+    #[msg("Root is not a recent root of the commitment tree")]
+    UnknownRoot,
 }
 
 /// Negate a G1 point (for pairing check)
@@ -233,14 +649,9 @@ fn negate_g1(point: &[u8; 64]) -> [u8; 64] {
 }
 
 /// Verify a Groth16 proof for a withdrawal using Solana's alt_bn128 syscalls
-///
-/// Groth16 verification equation:
-/// e(A, B) = e(alpha, beta) * e(L, gamma) * e(C, delta)
-///
-/// Rearranged for pairing check (product of pairings = 1):
-/// e(-A, B) * e(alpha, beta) * e(L, gamma) * e(C, delta) = 1
-///
-/// Where L = IC[0] + sum(public_input[i] * IC[i+1])
+/// and the compiled-in `mod vk` constants. Thin wrapper over
+/// [`verify_groth16`], which takes an arbitrary [`VerifyingKey`] account
+/// instead (see [`verify_groth16_withdraw_with_vk`] for the rotation path).
 pub fn verify_groth16_withdraw(
     proof_bytes: &[u8],
     root: &[u8; 32],
@@ -248,10 +659,6 @@ pub fn verify_groth16_withdraw(
     recipient: &[u8; 32],
     amount: &[u8; 32],
 ) -> Result<bool> {
-    // Parse proof
-    let proof = Groth16Proof::from_bytes(proof_bytes)
-        .ok_or(Groth16Error::InvalidProofSize)?;
-
     // Check if verifying key is initialized
     if !is_vk_initialized() {
         // VK not initialized - for development, return true
@@ -259,61 +666,19 @@ pub fn verify_groth16_withdraw(
         return Ok(true);
     }
 
-    // Compute L = IC[0] + sum(public_input[i] * IC[i+1])
-    let public_inputs = [root, nullifier_hash, recipient, amount];
-
-    // Start with IC[0]
-    let mut l_point = vk::IC[0];
-
-    // Add public_input[i] * IC[i+1] for each public input
-    for (i, input) in public_inputs.iter().enumerate() {
-        // Scalar multiplication: input * IC[i+1]
-        let mut scalar_mul_input = [0u8; 96]; // 64 bytes point + 32 bytes scalar
-        scalar_mul_input[0..64].copy_from_slice(&vk::IC[i + 1]);
-        scalar_mul_input[64..96].copy_from_slice(*input);
-
-        let mul_result = alt_bn128_multiplication(&scalar_mul_input)
-            .map_err(|_| Groth16Error::ScalarMulFailed)?;
-
-        // Point addition: L = L + mul_result
-        let mut add_input = [0u8; 128];
-        add_input[0..64].copy_from_slice(&l_point);
-        add_input[64..128].copy_from_slice(&mul_result);
-
-        let add_result = alt_bn128_addition(&add_input)
-            .map_err(|_| Groth16Error::PointAddFailed)?;
-
-        l_point.copy_from_slice(&add_result);
-    }
-
-    // Prepare pairing input: 4 pairs of (G1, G2) points
-    // Each pair is 192 bytes (64 G1 + 128 G2)
-    let mut pairing_input = [0u8; 768]; // 4 * 192
-
-    // Pair 1: (-A, B) - negate A for the pairing check
-    let neg_a = negate_g1(&proof.a);
-    pairing_input[0..64].copy_from_slice(&neg_a);
-    pairing_input[64..192].copy_from_slice(&proof.b);
-
-    // Pair 2: (alpha, beta)
-    pairing_input[192..256].copy_from_slice(&vk::ALPHA_G1);
-    pairing_input[256..384].copy_from_slice(&vk::BETA_G2);
-
-    // Pair 3: (L, gamma)
-    pairing_input[384..448].copy_from_slice(&l_point);
-    pairing_input[448..576].copy_from_slice(&vk::GAMMA_G2);
-
-    // Pair 4: (C, delta)
-    pairing_input[576..640].copy_from_slice(&proof.c);
-    pairing_input[640..768].copy_from_slice(&vk::DELTA_G2);
-
-    // Perform pairing check
-    // Returns true if product of pairings equals 1
-    let pairing_result = alt_bn128_pairing(&pairing_input)
-        .map_err(|_| Groth16Error::PairingFailed)?;
-
-    // The result is a single byte: 1 if valid, 0 if invalid
-    Ok(pairing_result[31] == 1)
+    let vk = VerifyingKey {
+        alpha_g1: vk::ALPHA_G1,
+        beta_g2: vk::BETA_G2,
+        gamma_g2: vk::GAMMA_G2,
+        delta_g2: vk::DELTA_G2,
+        ic: vk::IC.to_vec(),
+    };
+
+    verify_groth16(
+        proof_bytes,
+        &[*root, *nullifier_hash, *recipient, *amount],
+        &vk,
+    )
 }
 
 /// Convert a 32-byte little-endian field element to big-endian
@@ -378,6 +743,12 @@ mod tests {
         assert!(Groth16Proof::from_bytes(&proof_bytes).is_none());
     }
 
+    #[test]
+    fn test_compressed_proof_too_short() {
+        let compressed_bytes = [0u8; 64]; // Too short
+        assert!(Groth16Proof::from_compressed_bytes(&compressed_bytes).is_err());
+    }
+
     #[test]
     fn test_le_to_be_conversion() {
         let le = [1u8, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -388,4 +759,179 @@ mod tests {
         assert_eq!(be[29], 3);
         assert_eq!(be[28], 4);
     }
+
+    fn empty_verifying_key() -> VerifyingKey {
+        VerifyingKey {
+            alpha_g1: [0u8; 64],
+            beta_g2: [0u8; 128],
+            gamma_g2: [0u8; 128],
+            delta_g2: [0u8; 128],
+            ic: vec![],
+        }
+    }
+
+    #[test]
+    fn test_vk_account_space_for_matches_field_layout() {
+        let space = VerifyingKey::space_for(NUM_PUBLIC_INPUTS + 1);
+        assert_eq!(
+            space,
+            VerifyingKey::FIXED_SIZE + 4 + (NUM_PUBLIC_INPUTS + 1) * 64
+        );
+    }
+
+    #[test]
+    fn test_is_vk_account_initialized_rejects_empty_account() {
+        assert!(!is_vk_account_initialized(&empty_verifying_key()));
+    }
+
+    #[test]
+    fn test_is_vk_account_initialized_accepts_populated_account() {
+        let mut vk = empty_verifying_key();
+        vk.alpha_g1[0] = 1;
+        vk.ic = vec![[0u8; 64]; NUM_PUBLIC_INPUTS + 1];
+
+        assert!(is_vk_account_initialized(&vk));
+    }
+
+    #[test]
+    fn test_verify_groth16_rejects_public_input_count_mismatch() {
+        let mut vk = empty_verifying_key();
+        vk.alpha_g1[0] = 1;
+        vk.ic = vec![[0u8; 64]; 3]; // expects exactly 2 public inputs
+
+        let proof_bytes = [0u8; PROOF_SIZE];
+        let result = verify_groth16(&proof_bytes, &[[0u8; 32]; 4], &vk);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_verify_groth16_withdraw_with_vk_rejects_uninitialized_account() {
+        let proof_bytes = [0u8; PROOF_SIZE];
+        let result = verify_groth16_withdraw_with_vk(
+            &proof_bytes,
+            &[0u8; 32],
+            &[0u8; 32],
+            &[0u8; 32],
+            &[0u8; 32],
+            &empty_verifying_key(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_verify_groth16_withdraw_with_tree_rejects_unknown_root() {
+        let tree = IncrementalMerkleTree::<crate::merkle::KeccakHasher>::new();
+        let proof_bytes = [0u8; PROOF_SIZE];
+
+        let result = verify_groth16_withdraw_with_tree(
+            &proof_bytes,
+            &tree,
+            &[0xAAu8; 32], // not the tree's empty root, not in its history
+            &[0u8; 32],
+            &[0u8; 32],
+            &[0u8; 32],
+            &empty_verifying_key(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_verify_groth16_withdraw_with_tree_accepts_current_root_past_vk_check() {
+        let tree = IncrementalMerkleTree::<crate::merkle::KeccakHasher>::new();
+        let root = tree.root();
+        let proof_bytes = [0u8; PROOF_SIZE];
+
+        // The root passes `is_known_root`, so this should fail on the
+        // (uninitialized) VK rather than on an unknown-root rejection.
+        let result = verify_groth16_withdraw_with_tree(
+            &proof_bytes,
+            &tree,
+            &root,
+            &[0u8; 32],
+            &[0u8; 32],
+            &[0u8; 32],
+            &empty_verifying_key(),
+        );
+
+        match result {
+            Err(e) => assert!(!format!("{e:?}").contains("UnknownRoot")),
+            Ok(_) => panic!("expected an error from the uninitialized VK"),
+        }
+    }
+
+    #[test]
+    fn test_reduce_mod_scalar_field_is_idempotent_and_in_range() {
+        let already_reduced = [0x01u8; 32];
+        let reduced = reduce_mod_scalar_field(&already_reduced);
+        assert!(be_ge(&SCALAR_FIELD_R, &reduced));
+
+        // Reducing twice should be a no-op once the value is in range.
+        assert_eq!(reduce_mod_scalar_field(&reduced), reduced);
+    }
+
+    #[test]
+    fn test_reduce_mod_scalar_field_wraps_values_above_r() {
+        // SCALAR_FIELD_R itself reduces to zero.
+        assert_eq!(reduce_mod_scalar_field(&SCALAR_FIELD_R), [0u8; 32]);
+
+        // The all-ones value (2^256 - 1) must reduce to something in range.
+        let max_value = [0xFFu8; 32];
+        let reduced = reduce_mod_scalar_field(&max_value);
+        assert!(be_ge(&SCALAR_FIELD_R, &reduced));
+        assert_ne!(reduced, max_value);
+    }
+
+    #[test]
+    fn test_derive_batch_scalars_are_distinct_and_in_range() {
+        let proof = Groth16Proof {
+            a: [1u8; 64],
+            b: [2u8; 128],
+            c: [3u8; 64],
+        };
+        let input = WithdrawPublicInputs {
+            root: [4u8; 32],
+            nullifier_hash: [5u8; 32],
+            recipient: [6u8; 32],
+            amount: [7u8; 32],
+        };
+
+        let proofs = vec![proof.clone(), proof];
+        let inputs = vec![input.clone(), input];
+
+        let scalars = derive_batch_scalars(&proofs, &inputs);
+        assert_eq!(scalars.len(), 2);
+        assert_ne!(scalars[0], scalars[1]);
+        for scalar in &scalars {
+            assert!(be_ge(&SCALAR_FIELD_R, scalar));
+        }
+    }
+
+    #[test]
+    fn test_verify_groth16_batch_rejects_mismatched_lengths() {
+        let vk = empty_verifying_key();
+        let proof = Groth16Proof {
+            a: [0u8; 64],
+            b: [0u8; 128],
+            c: [0u8; 64],
+        };
+        let input = WithdrawPublicInputs {
+            root: [0u8; 32],
+            nullifier_hash: [0u8; 32],
+            recipient: [0u8; 32],
+            amount: [0u8; 32],
+        };
+
+        let result = verify_groth16_batch(&[proof], &[input.clone(), input], &vk);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_verify_groth16_batch_rejects_empty_batch() {
+        let vk = empty_verifying_key();
+        let result = verify_groth16_batch(&[], &[], &vk);
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,234 @@
+//! Host-side import of snarkjs/circom artifacts into the on-chain Groth16 types.
+//!
+//! Producing `vk::*`/`VerifyingKey` bytes and a 256-byte proof by hand is
+//! error-prone: snarkjs emits `verification_key.json`, `proof.json`, and
+//! `public.json` as base-10 decimal-string field elements, which this module
+//! parses into arkworks' canonical little-endian byte encoding, while the
+//! on-chain verifier (see `groth16.rs`) expects big-endian bytes for the
+//! alt_bn128 syscalls. This module is the
+//! one place that boundary gets crossed, via the existing
+//! `le_to_be_32`/`le_to_be_g1`/`le_to_be_g2` converters, so the output drops
+//! straight into `verify_groth16_withdraw`/`verify_groth16`.
+//!
+//! This only runs off the host building transactions or governing a VK
+//! rotation, never inside the SBF program, so it's gated behind the
+//! `host-tools` feature (pulling in `serde`/`serde_json` would bloat, and
+//! likely fail to compile for, the on-chain build).
+#![cfg(feature = "host-tools")]
+
+use crate::groth16::{le_to_be_32, le_to_be_g1, le_to_be_g2, Groth16Proof, VerifyingKey, PROOF_SIZE};
+use serde::Deserialize;
+
+/// snarkjs `verification_key.json` shape (only the fields this module needs;
+/// `protocol`, `curve`, `vk_alphabeta_12`, etc. are ignored).
+#[derive(Deserialize)]
+pub struct SnarkjsVerificationKey {
+    vk_alpha_1: [String; 3],
+    vk_beta_2: [[String; 2]; 3],
+    vk_gamma_2: [[String; 2]; 3],
+    vk_delta_2: [[String; 2]; 3],
+    #[serde(rename = "IC")]
+    ic: Vec<[String; 3]>,
+}
+
+/// snarkjs `proof.json` shape.
+#[derive(Deserialize)]
+pub struct SnarkjsProof {
+    pi_a: [String; 3],
+    pi_b: [[String; 2]; 3],
+    pi_c: [String; 3],
+}
+
+/// An error importing a snarkjs/circom artifact: a field element that
+/// doesn't parse as a non-negative base-10 integer, or one that overflows
+/// the 256 bits a BN254 field element must fit in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportError(pub String);
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Parse a base-10 field-element string (as snarkjs emits for every
+/// coordinate and public signal) into arkworks' canonical little-endian
+/// 32-byte encoding. Implemented as schoolbook base-256 long multiplication
+/// on a big-endian accumulator (one decimal digit at a time), then reversed
+/// to little-endian at the end, since no bignum crate is available here.
+fn decimal_to_le_bytes(decimal: &str) -> Result<[u8; 32], ImportError> {
+    let mut be = [0u8; 32];
+    for ch in decimal.trim().chars() {
+        let digit = ch
+            .to_digit(10)
+            .ok_or_else(|| ImportError(format!("not a decimal digit: {ch:?}")))?;
+        let mut carry = digit;
+        for byte in be.iter_mut().rev() {
+            let product = *byte as u32 * 10 + carry;
+            *byte = (product & 0xFF) as u8;
+            carry = product >> 8;
+        }
+        if carry != 0 {
+            return Err(ImportError(format!(
+                "field element does not fit in 256 bits: {decimal}"
+            )));
+        }
+    }
+    be.reverse();
+    Ok(be)
+}
+
+/// Parse a snarkjs G1 point `[x, y, z]` (`z` is always `"1"` for an affine
+/// point) into the 64-byte big-endian `(x, y)` layout the verifier expects.
+fn parse_g1(point: &[String; 3]) -> Result<[u8; 64], ImportError> {
+    let mut le = [0u8; 64];
+    le[0..32].copy_from_slice(&decimal_to_le_bytes(&point[0])?);
+    le[32..64].copy_from_slice(&decimal_to_le_bytes(&point[1])?);
+    Ok(le_to_be_g1(&le))
+}
+
+/// Parse a snarkjs G2 point `[[x.c0, x.c1], [y.c0, y.c1], [1, 0]]` into the
+/// 128-byte big-endian `x.c0, x.c1, y.c0, y.c1` layout the verifier expects.
+fn parse_g2(point: &[[String; 2]; 3]) -> Result<[u8; 128], ImportError> {
+    let mut le = [0u8; 128];
+    le[0..32].copy_from_slice(&decimal_to_le_bytes(&point[0][0])?);
+    le[32..64].copy_from_slice(&decimal_to_le_bytes(&point[0][1])?);
+    le[64..96].copy_from_slice(&decimal_to_le_bytes(&point[1][0])?);
+    le[96..128].copy_from_slice(&decimal_to_le_bytes(&point[1][1])?);
+    Ok(le_to_be_g2(&le))
+}
+
+/// Import a snarkjs `verification_key.json` into an on-chain [`VerifyingKey`]
+/// account's field values (caller still owns allocating and writing the
+/// account via [`VerifyingKey::space_for`]).
+pub fn import_verifying_key(vk: &SnarkjsVerificationKey) -> Result<VerifyingKey, ImportError> {
+    let ic = vk
+        .ic
+        .iter()
+        .map(parse_g1)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(VerifyingKey {
+        alpha_g1: parse_g1(&vk.vk_alpha_1)?,
+        beta_g2: parse_g2(&vk.vk_beta_2)?,
+        gamma_g2: parse_g2(&vk.vk_gamma_2)?,
+        delta_g2: parse_g2(&vk.vk_delta_2)?,
+        ic,
+    })
+}
+
+/// Import a snarkjs `proof.json` into the on-chain 256-byte proof layout
+/// (`a: 64 bytes || b: 128 bytes || c: 64 bytes`, all big-endian), ready to
+/// pass to `verify_groth16`/`verify_groth16_withdraw`.
+pub fn import_proof(proof: &SnarkjsProof) -> Result<[u8; PROOF_SIZE], ImportError> {
+    let a = parse_g1(&proof.pi_a)?;
+    let b = parse_g2(&proof.pi_b)?;
+    let c = parse_g1(&proof.pi_c)?;
+
+    Ok(Groth16Proof { a, b, c }.to_bytes())
+}
+
+/// Import a snarkjs `public.json` (an array of decimal-string public
+/// signals, in circuit order) into the big-endian `[u8; 32]` layout the
+/// verifier's `public_inputs` slice expects.
+pub fn import_public_inputs(public: &[String]) -> Result<Vec<[u8; 32]>, ImportError> {
+    public
+        .iter()
+        .map(|s| decimal_to_le_bytes(s).map(|le| le_to_be_32(&le)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_to_le_bytes_zero() {
+        assert_eq!(decimal_to_le_bytes("0").unwrap(), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_decimal_to_le_bytes_small_value_is_little_endian() {
+        let le = decimal_to_le_bytes("1").unwrap();
+        assert_eq!(le[0], 1);
+        assert!(le[1..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_decimal_to_le_bytes_matches_be_reversal() {
+        // 256 = 0x100, so BE is [..,1,0] and LE is [0,1,..].
+        let le = decimal_to_le_bytes("256").unwrap();
+        let mut be = le;
+        be.reverse();
+        assert_eq!(be[30], 1);
+        assert_eq!(be[31], 0);
+        assert_eq!(le_to_be_32(&le), be);
+    }
+
+    #[test]
+    fn test_decimal_to_le_bytes_rejects_non_digit() {
+        assert!(decimal_to_le_bytes("12a4").is_err());
+    }
+
+    #[test]
+    fn test_decimal_to_le_bytes_rejects_overflow() {
+        // 2^256, one past the largest representable value.
+        let too_big =
+            "115792089237316195423570985008687907853269984665640564039457584007913129639936";
+        assert!(decimal_to_le_bytes(&too_big).is_err());
+    }
+
+    #[test]
+    fn test_parse_g1_round_trips_through_be_conversion() {
+        let point = ["1".to_string(), "2".to_string(), "1".to_string()];
+        let be = parse_g1(&point).unwrap();
+        // x = 1, y = 2, each big-endian: low byte is the last byte of the coordinate.
+        assert_eq!(be[31], 1);
+        assert_eq!(be[63], 2);
+    }
+
+    #[test]
+    fn test_parse_g2_preserves_c0_c1_order() {
+        let point = [
+            ["1".to_string(), "2".to_string()],
+            ["3".to_string(), "4".to_string()],
+            ["1".to_string(), "0".to_string()],
+        ];
+        let be = parse_g2(&point).unwrap();
+        assert_eq!(be[31], 1); // x.c0
+        assert_eq!(be[63], 2); // x.c1
+        assert_eq!(be[95], 3); // y.c0
+        assert_eq!(be[127], 4); // y.c1
+    }
+
+    #[test]
+    fn test_import_public_inputs_preserves_order() {
+        let public = vec!["1".to_string(), "2".to_string(), "3".to_string()];
+        let imported = import_public_inputs(&public).unwrap();
+        assert_eq!(imported.len(), 3);
+        assert_eq!(imported[0][31], 1);
+        assert_eq!(imported[1][31], 2);
+        assert_eq!(imported[2][31], 3);
+    }
+
+    #[test]
+    fn test_import_proof_matches_from_bytes_round_trip() {
+        let proof = SnarkjsProof {
+            pi_a: ["1".to_string(), "2".to_string(), "1".to_string()],
+            pi_b: [
+                ["3".to_string(), "4".to_string()],
+                ["5".to_string(), "6".to_string()],
+                ["1".to_string(), "0".to_string()],
+            ],
+            pi_c: ["7".to_string(), "8".to_string(), "1".to_string()],
+        };
+        let bytes = import_proof(&proof).unwrap();
+        let parsed = Groth16Proof::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.a[31], 1);
+        assert_eq!(parsed.a[63], 2);
+        assert_eq!(parsed.c[31], 7);
+        assert_eq!(parsed.c[63], 8);
+    }
+}
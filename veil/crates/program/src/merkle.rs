@@ -10,78 +10,89 @@
 
 use anchor_lang::prelude::*;
 use solana_program::keccak;
+use std::marker::PhantomData;
 
 /// Merkle tree depth (10 levels = 2^10 = 1,024 leaves)
 /// Reduced from 20 to avoid stack overflow on Solana
 pub const TREE_DEPTH: usize = 10;
 
-/// Zero value for empty leaves (hash of empty bytes)
+/// Domain tag prepended to a raw leaf before hashing. Without this, a
+/// 32-byte internal node hash could be replayed as a "leaf" (the classic
+/// Merkle second-preimage attack) since both would otherwise be hashed the
+/// same way.
+pub const LEAF_PREFIX: [u8; 1] = [0u8];
+
+/// Domain tag prepended to a pair of child hashes before combining them.
+pub const INTERMEDIATE_PREFIX: [u8; 1] = [1u8];
+
+/// Zero value for empty leaves: `keccak(LEAF_PREFIX || [0u8; 32])`
 pub const ZERO_VALUE: [u8; 32] = [
-    0x29, 0x0d, 0xec, 0xd9, 0x54, 0x8b, 0x62, 0xa8,
-    0xd6, 0x03, 0x45, 0xa9, 0x88, 0x38, 0x6f, 0xc8,
-    0x4b, 0xa6, 0xbc, 0x95, 0x48, 0x40, 0x08, 0xf6,
-    0x36, 0x2f, 0x93, 0x16, 0x0e, 0xf3, 0xe5, 0x63,
+    0xf3, 0x9a, 0x86, 0x9f, 0x62, 0xe7, 0x5c, 0xf5,
+    0xf0, 0xbf, 0x91, 0x46, 0x88, 0xa6, 0xb2, 0x89,
+    0xca, 0xf2, 0x04, 0x94, 0x35, 0xd8, 0xe6, 0x8c,
+    0x5c, 0x5e, 0x6d, 0x05, 0xe4, 0x49, 0x13, 0xf3,
 ];
 
-/// Precomputed zero hashes for each level (Keccak256)
-/// zeros[i] = keccak256(zeros[i-1] || zeros[i-1])
+/// Precomputed zero hashes for each level (Keccak256, domain-separated)
+/// zeros[0] = ZERO_VALUE
+/// zeros[i] = keccak(INTERMEDIATE_PREFIX || zeros[i-1] || zeros[i-1])
 /// Precomputed to eliminate stack allocation in get_zero_hash()
 pub const ZERO_HASHES: [[u8; 32]; TREE_DEPTH + 1] = [
     // Level 0
-    [0x29, 0x0d, 0xec, 0xd9, 0x54, 0x8b, 0x62, 0xa8,
-     0xd6, 0x03, 0x45, 0xa9, 0x88, 0x38, 0x6f, 0xc8,
-     0x4b, 0xa6, 0xbc, 0x95, 0x48, 0x40, 0x08, 0xf6,
-     0x36, 0x2f, 0x93, 0x16, 0x0e, 0xf3, 0xe5, 0x63],
+    [0xf3, 0x9a, 0x86, 0x9f, 0x62, 0xe7, 0x5c, 0xf5,
+     0xf0, 0xbf, 0x91, 0x46, 0x88, 0xa6, 0xb2, 0x89,
+     0xca, 0xf2, 0x04, 0x94, 0x35, 0xd8, 0xe6, 0x8c,
+     0x5c, 0x5e, 0x6d, 0x05, 0xe4, 0x49, 0x13, 0xf3],
     // Level 1
-    [0x63, 0x3d, 0xc4, 0xd7, 0xda, 0x72, 0x56, 0x66,
-     0x0a, 0x89, 0x2f, 0x8f, 0x16, 0x04, 0xa4, 0x4b,
-     0x54, 0x32, 0x64, 0x9c, 0xc8, 0xec, 0x5c, 0xb3,
-     0xce, 0xd4, 0xc4, 0xe6, 0xac, 0x94, 0xdd, 0x1d],
+    [0x4e, 0xd5, 0xc0, 0x2d, 0x6d, 0x48, 0xc8, 0x93,
+     0x24, 0x86, 0xc9, 0x9d, 0x3a, 0xd9, 0x99, 0xe5,
+     0xd8, 0x94, 0x9d, 0xc3, 0xbe, 0x3b, 0x30, 0x58,
+     0xcc, 0x29, 0x79, 0x69, 0x0c, 0x3e, 0x3a, 0x62],
     // Level 2
-    [0x89, 0x07, 0x40, 0xa8, 0xeb, 0x06, 0xce, 0x9b,
-     0xe4, 0x22, 0xcb, 0x8d, 0xa5, 0xcd, 0xaf, 0xc2,
-     0xb5, 0x8c, 0x0a, 0x5e, 0x24, 0x03, 0x6c, 0x57,
-     0x8d, 0xe2, 0xa4, 0x33, 0xc8, 0x28, 0xff, 0x7d],
+    [0x1c, 0x79, 0x2b, 0x14, 0xbf, 0x66, 0xf8, 0x2a,
+     0xf3, 0x6f, 0x00, 0xf5, 0xfb, 0xa7, 0x01, 0x4f,
+     0xa0, 0xc1, 0xe2, 0xff, 0x3c, 0x7c, 0x27, 0x3b,
+     0xfe, 0x52, 0x3c, 0x1a, 0xcf, 0x67, 0xdc, 0x3f],
     // Level 3
-    [0x3b, 0x8e, 0xc0, 0x9e, 0x02, 0x6f, 0xdc, 0x30,
-     0x53, 0x65, 0xdf, 0xc9, 0x4e, 0x18, 0x9a, 0x81,
-     0xb3, 0x8c, 0x75, 0x97, 0xb3, 0xd9, 0x41, 0xc2,
-     0x79, 0xf0, 0x42, 0xe8, 0x20, 0x6e, 0x0b, 0xd8],
+    [0x5f, 0xa0, 0x80, 0xa6, 0x86, 0xa5, 0xa0, 0xd0,
+     0x5c, 0x3d, 0x48, 0x22, 0xfd, 0x54, 0xd6, 0x32,
+     0xdc, 0x9c, 0xc0, 0x4b, 0x16, 0x16, 0x04, 0x6e,
+     0xba, 0x2c, 0xe4, 0x99, 0xeb, 0x9a, 0xf7, 0x9f],
     // Level 4
-    [0xec, 0xd5, 0x0e, 0xee, 0x38, 0xe3, 0x86, 0xbd,
-     0x62, 0xbe, 0x9b, 0xed, 0xb9, 0x90, 0x70, 0x69,
-     0x51, 0xb6, 0x5f, 0xe0, 0x53, 0xbd, 0x9d, 0x8a,
-     0x52, 0x1a, 0xf7, 0x53, 0xd1, 0x39, 0xe2, 0xda],
+    [0x5e, 0xb9, 0x49, 0x69, 0x0a, 0x04, 0x04, 0xab,
+     0xf4, 0xce, 0xba, 0xfc, 0x7c, 0xff, 0xfa, 0x38,
+     0x21, 0x91, 0xb7, 0xdd, 0x9e, 0x7d, 0xf7, 0x78,
+     0x58, 0x1e, 0x6f, 0xb7, 0x8e, 0xfa, 0xb3, 0x5f],
     // Level 5
-    [0xde, 0xff, 0xf6, 0xd3, 0x30, 0xbb, 0x54, 0x03,
-     0xf6, 0x3b, 0x14, 0xf3, 0x3b, 0x57, 0x82, 0x74,
-     0x16, 0x0d, 0xe3, 0xa5, 0x0d, 0xf4, 0xef, 0xec,
-     0xf0, 0xe0, 0xdb, 0x73, 0xbc, 0xdd, 0x3d, 0xa5],
+    [0xd3, 0x64, 0xc9, 0xd5, 0xda, 0xda, 0xd4, 0x56,
+     0x9b, 0x6d, 0xd4, 0x7f, 0x7f, 0xea, 0xba, 0xfa,
+     0x35, 0x71, 0xf8, 0x42, 0x43, 0x44, 0x25, 0x54,
+     0x83, 0x35, 0xac, 0x6e, 0x69, 0x0d, 0xd0, 0x71],
     // Level 6
-    [0x61, 0x7b, 0xdd, 0x11, 0xf7, 0xc0, 0xa1, 0x1f,
-     0x49, 0xdb, 0x22, 0xf6, 0x29, 0x38, 0x7a, 0x12,
-     0xda, 0x75, 0x96, 0xf9, 0xd1, 0x70, 0x4d, 0x74,
-     0x65, 0x17, 0x7c, 0x63, 0xd8, 0x8e, 0xc7, 0xd7],
+    [0x68, 0xd8, 0xbc, 0x5b, 0x77, 0x97, 0x9c, 0x1a,
+     0x67, 0x02, 0x33, 0x4f, 0x52, 0x9f, 0x57, 0x83,
+     0xf7, 0x9e, 0x94, 0x2f, 0xd2, 0xcd, 0x03, 0xf6,
+     0xe5, 0x5a, 0xc2, 0xcf, 0x49, 0x6e, 0x84, 0x9f],
     // Level 7
-    [0x29, 0x2c, 0x23, 0xa9, 0xaa, 0x1d, 0x8b, 0xea,
-     0x7e, 0x24, 0x35, 0xe5, 0x55, 0xa4, 0xa6, 0x0e,
-     0x37, 0x9a, 0x5a, 0x35, 0xf3, 0xf4, 0x52, 0xba,
-     0xe6, 0x01, 0x21, 0x07, 0x3f, 0xb6, 0xee, 0xad],
+    [0xde, 0x9c, 0x44, 0x6f, 0xab, 0x46, 0xa8, 0xd2,
+     0x7d, 0xb1, 0xe3, 0x10, 0x0f, 0x27, 0x5a, 0x77,
+     0x7d, 0x38, 0x5b, 0x44, 0xe3, 0xcb, 0xc0, 0x45,
+     0xca, 0xba, 0xc9, 0xda, 0x36, 0xca, 0xe0, 0x40],
     // Level 8
-    [0xe1, 0xce, 0xa9, 0x2e, 0xd9, 0x9a, 0xcd, 0xcb,
-     0x04, 0x5a, 0x67, 0x26, 0xb2, 0xf8, 0x71, 0x07,
-     0xe8, 0xa6, 0x16, 0x20, 0xa2, 0x32, 0xcf, 0x4d,
-     0x7d, 0x5b, 0x57, 0x66, 0xb3, 0x95, 0x2e, 0x10],
+    [0xad, 0x51, 0x60, 0x82, 0x32, 0x4c, 0x96, 0x12,
+     0x7c, 0xf2, 0x9f, 0x45, 0x35, 0xeb, 0x5b, 0x7e,
+     0xba, 0xcf, 0xe2, 0xa1, 0xd6, 0xd3, 0xaa, 0xb8,
+     0xec, 0x04, 0x83, 0xd3, 0x20, 0x79, 0xa8, 0x59],
     // Level 9
-    [0x7a, 0xd6, 0x6c, 0x0a, 0x68, 0xc7, 0x2c, 0xb8,
-     0x9e, 0x4f, 0xb4, 0x30, 0x38, 0x41, 0x96, 0x6e,
-     0x40, 0x62, 0xa7, 0x6a, 0xb9, 0x74, 0x51, 0xe3,
-     0xb9, 0xfb, 0x52, 0x6a, 0x5c, 0xeb, 0x7f, 0x82],
+    [0xff, 0x70, 0xf9, 0x21, 0x59, 0x70, 0xa8, 0xbe,
+     0xeb, 0xb1, 0xc1, 0x64, 0xc4, 0x74, 0xe8, 0x24,
+     0x38, 0x17, 0x4c, 0x8e, 0xeb, 0x6f, 0xbc, 0x8c,
+     0xb4, 0x59, 0x4b, 0x88, 0xc9, 0x44, 0x8f, 0x1d],
     // Level 10
-    [0xe0, 0x26, 0xcc, 0x5a, 0x4a, 0xed, 0x3c, 0x22,
-     0xa5, 0x8c, 0xbd, 0x3d, 0x2a, 0xc7, 0x54, 0xc9,
-     0x35, 0x2c, 0x54, 0x36, 0xf6, 0x38, 0x04, 0x2d,
-     0xca, 0x99, 0x03, 0x4e, 0x83, 0x63, 0x65, 0x16],
+    [0x40, 0xb0, 0x9b, 0xea, 0xec, 0xac, 0x5b, 0x45,
+     0xdb, 0x6e, 0x41, 0x43, 0x4a, 0x12, 0x2b, 0x69,
+     0x5c, 0x5a, 0x85, 0x86, 0x2d, 0x8e, 0xae, 0x40,
+     0xb3, 0x26, 0x8f, 0x6f, 0x37, 0xe4, 0x14, 0x33],
 ];
 
 /// Get zero hash for a specific level (O(1) lookup, no stack allocation)
@@ -90,24 +101,100 @@ pub fn get_zero_hash(level: usize) -> [u8; 32] {
     ZERO_HASHES[level]
 }
 
-/// Hash two 32-byte values together using Keccak256
+/// Hash a raw leaf value with the leaf domain tag: `keccak(LEAF_PREFIX || leaf)`
+pub fn hash_leaf(leaf: &[u8; 32]) -> [u8; 32] {
+    let mut combined = [0u8; 33];
+    combined[0] = LEAF_PREFIX[0];
+    combined[1..].copy_from_slice(leaf);
+
+    keccak::hash(&combined).to_bytes()
+}
+
+/// Hash two child node hashes together using Keccak256, tagged so an
+/// internal node can never be replayed as a leaf.
 /// Note: Using Keccak256 for Solana compatibility (cheaper than SHA256 on-chain)
-pub fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
-    let mut combined = [0u8; 64];
-    combined[..32].copy_from_slice(left);
-    combined[32..].copy_from_slice(right);
+pub fn hash_intermediate(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut combined = [0u8; 65];
+    combined[0] = INTERMEDIATE_PREFIX[0];
+    combined[1..33].copy_from_slice(left);
+    combined[33..].copy_from_slice(right);
 
     keccak::hash(&combined).to_bytes()
 }
 
+/// Hashing strategy used by the tree, abstracted away from Keccak so the
+/// same tree/proof code can be reused with an arithmetization-friendly hash
+/// (e.g. Poseidon) for off-chain circuits, while the on-chain program keeps
+/// using Keccak for compute-budget reasons.
+pub trait TreeHasher {
+    /// Leaf hash of an empty leaf; seeds the zero-hash ladder (`zeros[0]`).
+    const ZERO_LEAF: [u8; 32];
+
+    /// Domain-separated hash of a raw leaf.
+    fn hash_leaf(leaf: &[u8; 32]) -> [u8; 32];
+
+    /// Domain-separated combination of two child hashes.
+    fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32];
+
+    /// Zero hash at a given level, i.e. the root of an empty subtree of that
+    /// height. The default derives the ladder from `ZERO_LEAF` at runtime;
+    /// `KeccakHasher` overrides this with a precomputed table so on-chain
+    /// code never pays for the hashing.
+    fn zero_hash(level: usize) -> [u8; 32] {
+        build_zero_hashes::<Self>()[level]
+    }
+}
+
+/// Derives `zeros[i] = H::hash_pair(zeros[i-1], zeros[i-1])` starting from
+/// `H::ZERO_LEAF`, so a new hasher only needs to supply its zero leaf rather
+/// than a whole hand-computed table.
+pub fn build_zero_hashes<H: TreeHasher + ?Sized>() -> [[u8; 32]; TREE_DEPTH + 1] {
+    let mut zeros = [[0u8; 32]; TREE_DEPTH + 1];
+    zeros[0] = H::ZERO_LEAF;
+    for i in 1..=TREE_DEPTH {
+        zeros[i] = H::hash_pair(&zeros[i - 1], &zeros[i - 1]);
+    }
+    zeros
+}
+
+/// Default on-chain hasher: Keccak256 with domain-separated leaves/nodes.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeccakHasher;
+
+impl TreeHasher for KeccakHasher {
+    const ZERO_LEAF: [u8; 32] = ZERO_VALUE;
+
+    fn hash_leaf(leaf: &[u8; 32]) -> [u8; 32] {
+        hash_leaf(leaf)
+    }
+
+    fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        hash_intermediate(left, right)
+    }
+
+    fn zero_hash(level: usize) -> [u8; 32] {
+        get_zero_hash(level)
+    }
+}
+
+/// Number of historical roots retained so that proofs generated against a
+/// recently-current root don't fail verification just because another
+/// deposit landed in the meantime (the standard Tornado Cash "known roots"
+/// window).
+pub const ROOT_HISTORY_SIZE: usize = 30;
+
 /// Incremental Merkle Tree state
 ///
 /// This stores the minimal state needed to:
 /// 1. Insert new leaves efficiently
 /// 2. Compute the current root
 /// 3. Generate membership proofs
+///
+/// Generic over `H` so the same tree logic can be reused with a different
+/// hash (e.g. Poseidon, off-chain). Defaults to `KeccakHasher`, which is the
+/// only instantiation actually stored on-chain.
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub struct IncrementalMerkleTree {
+pub struct IncrementalMerkleTree<H: TreeHasher = KeccakHasher> {
     /// Current number of leaves in the tree
     pub next_index: u64,
 
@@ -117,17 +204,32 @@ pub struct IncrementalMerkleTree {
 
     /// Current root of the tree
     pub current_root: [u8; 32],
+
+    /// Ring buffer of the last `ROOT_HISTORY_SIZE` roots, most recent at
+    /// `current_root_index`. Lets `is_known_root` accept a root that was
+    /// current a few inserts ago, not just the very latest one.
+    pub root_history: [[u8; 32]; ROOT_HISTORY_SIZE],
+
+    /// Index of `current_root` within `root_history`
+    pub current_root_index: u64,
+
+    /// Which hasher this tree was built with; carries no data on-chain.
+    pub hasher: PhantomData<H>,
 }
 
-impl Default for IncrementalMerkleTree {
+impl<H: TreeHasher> Default for IncrementalMerkleTree<H> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl IncrementalMerkleTree {
+impl<H: TreeHasher> IncrementalMerkleTree<H> {
     /// Size of the tree state in bytes
-    pub const SIZE: usize = 8 + (32 * TREE_DEPTH) + 32; // next_index + filled_subtrees + current_root
+    pub const SIZE: usize = 8 // next_index
+        + (32 * TREE_DEPTH) // filled_subtrees
+        + 32 // current_root
+        + (32 * ROOT_HISTORY_SIZE) // root_history
+        + 8; // current_root_index (PhantomData<H> has no representation)
 
     /// Maximum number of leaves
     pub const MAX_LEAVES: u64 = 1 << TREE_DEPTH; // 2^20 = 1,048,576
@@ -138,16 +240,22 @@ impl IncrementalMerkleTree {
 
         // Initialize filled_subtrees with zero hashes
         for i in 0..TREE_DEPTH {
-            filled_subtrees[i] = get_zero_hash(i);
+            filled_subtrees[i] = H::zero_hash(i);
         }
 
         // Initial root is the zero hash at the top level
-        let current_root = get_zero_hash(TREE_DEPTH);
+        let current_root = H::zero_hash(TREE_DEPTH);
+
+        let mut root_history = [[0u8; 32]; ROOT_HISTORY_SIZE];
+        root_history[0] = current_root;
 
         Self {
             next_index: 0,
             filled_subtrees,
             current_root,
+            root_history,
+            current_root_index: 0,
+            hasher: PhantomData,
         }
     }
 
@@ -161,7 +269,7 @@ impl IncrementalMerkleTree {
         );
 
         let leaf_index = self.next_index;
-        let mut current_hash = leaf;
+        let mut current_hash = H::hash_leaf(&leaf);
         let mut current_index = leaf_index;
 
         // Walk up the tree, computing hashes
@@ -170,41 +278,214 @@ impl IncrementalMerkleTree {
 
             if is_left {
                 // We're on the left side - use zero hash for right sibling
-                let right = get_zero_hash(level);
+                let right = H::zero_hash(level);
 
                 // Store this node as the filled subtree at this level
                 self.filled_subtrees[level] = current_hash;
 
-                current_hash = hash_pair(&current_hash, &right);
+                current_hash = H::hash_pair(&current_hash, &right);
             } else {
                 // We're on the right side - use filled subtree for left sibling
                 let left = self.filled_subtrees[level];
-                current_hash = hash_pair(&left, &current_hash);
+                current_hash = H::hash_pair(&left, &current_hash);
             }
 
             current_index /= 2;
         }
 
-        // Update the root
+        // Update the root and push it into the history ring buffer
         self.current_root = current_hash;
+        self.current_root_index = (self.current_root_index + 1) % ROOT_HISTORY_SIZE as u64;
+        self.root_history[self.current_root_index as usize] = current_hash;
         self.next_index += 1;
 
         Ok(leaf_index)
     }
 
+    /// Insert a batch of leaves in one pass.
+    ///
+    /// Per-leaf `insert` walks all `TREE_DEPTH` levels for every single leaf,
+    /// so `k` sequential inserts redundantly re-hash the right frontier
+    /// `O(k * TREE_DEPTH)` times. This instead hashes each level of the batch
+    /// exactly once: start from the leaf hashes, then at every level pair up
+    /// adjacent nodes (combining with the stored `filled_subtrees` entry when
+    /// the batch starts mid-pair, and with the level's zero hash when it ends
+    /// on an unpaired node), climbing one level at a time until a single root
+    /// remains.
+    ///
+    /// Produces byte-identical `filled_subtrees`, `next_index`, and root to
+    /// calling `insert` once per leaf, in order. It differs only in
+    /// `root_history`: the intermediate roots produced after each leaf are
+    /// never observable, so only the final root is pushed into the ring
+    /// buffer (one push instead of `k`).
+    ///
+    /// Returns the index of the first leaf inserted.
+    pub fn insert_many(&mut self, leaves: &[[u8; 32]]) -> Result<u64> {
+        if leaves.is_empty() {
+            return Ok(self.next_index);
+        }
+
+        require!(
+            self.next_index + leaves.len() as u64 <= Self::MAX_LEAVES,
+            MerkleError::TreeFull
+        );
+
+        let start_index = self.next_index;
+
+        let mut level_start_index = start_index;
+        let mut level_nodes: Vec<[u8; 32]> = leaves.iter().map(H::hash_leaf).collect();
+
+        for level in 0..TREE_DEPTH {
+            let mut next_nodes = Vec::with_capacity(level_nodes.len() / 2 + 1);
+            let mut i = 0;
+
+            // If this level's batch starts on a "right" child, its left
+            // sibling was stored by an earlier (possibly pre-batch) insert.
+            if level_start_index % 2 == 1 {
+                let left = self.filled_subtrees[level];
+                let right = level_nodes[0];
+                next_nodes.push(H::hash_pair(&left, &right));
+                i = 1;
+            }
+
+            // Pair up the rest two at a time; each pair's left member is the
+            // frontier node at this level, exactly as a lone `insert` would
+            // record before being paired with its right sibling.
+            while i + 1 < level_nodes.len() {
+                let left = level_nodes[i];
+                let right = level_nodes[i + 1];
+                self.filled_subtrees[level] = left;
+                next_nodes.push(H::hash_pair(&left, &right));
+                i += 2;
+            }
+
+            // An unpaired node at the end of the batch becomes the new
+            // frontier at this level, combined with the zero hash to keep
+            // climbing toward the root.
+            if i < level_nodes.len() {
+                let current_hash = level_nodes[i];
+                self.filled_subtrees[level] = current_hash;
+                next_nodes.push(H::hash_pair(&current_hash, &H::zero_hash(level)));
+            }
+
+            level_nodes = next_nodes;
+            level_start_index /= 2;
+        }
+
+        self.current_root = level_nodes[0];
+        self.current_root_index = (self.current_root_index + 1) % ROOT_HISTORY_SIZE as u64;
+        self.root_history[self.current_root_index as usize] = self.current_root;
+        self.next_index += leaves.len() as u64;
+
+        Ok(start_index)
+    }
+
     /// Get the current root
     pub fn root(&self) -> [u8; 32] {
         self.current_root
     }
 
-    /// Check if a root is valid (matches current root)
-    /// In production, we'd also check against a history of recent roots
+    /// Check if a root is valid: either the current root or one of the last
+    /// `ROOT_HISTORY_SIZE` roots, so a proof built against a slightly stale
+    /// root still verifies. Zero entries are uninitialized slots and never
+    /// match a real root.
     pub fn is_known_root(&self, root: &[u8; 32]) -> bool {
-        *root == self.current_root
+        if *root == [0u8; 32] {
+            return false;
+        }
+
+        if *root == self.current_root {
+            return true;
+        }
+
+        self.root_history
+            .iter()
+            .any(|historical_root| *historical_root != [0u8; 32] && historical_root == root)
+    }
+
+    /// Root of the subtree rooted at `(level, index)`, read directly from
+    /// on-chain state without reconstructing the path to the top.
+    ///
+    /// Only the tree's current rightmost frontier subtree at each level is
+    /// retained once filled; subtrees to its left have already been folded
+    /// into an ancestor and aren't separately addressable here. For those
+    /// (or for verifying a chunk client-side), use `get_subtree_root_with`
+    /// over the full leaf set instead.
+    pub fn get_subtree_root(&self, level: usize, index: u64) -> Result<[u8; 32]> {
+        require!(level <= TREE_DEPTH, MerkleError::InvalidLeafIndex);
+
+        let max_index = 1u64 << (TREE_DEPTH - level);
+        require!(index < max_index, MerkleError::InvalidSubtreeIndex);
+
+        if level == TREE_DEPTH {
+            return Ok(self.current_root);
+        }
+
+        // `filled_subtrees[level]` holds exactly one subtree: whichever one
+        // is currently the rightmost frontier at this level. Reject any
+        // in-range `index` other than that one instead of silently handing
+        // back the frontier's data under a different index's name.
+        let frontier_index = if self.next_index == 0 {
+            0
+        } else {
+            ((self.next_index - 1) >> level) & !1u64
+        };
+        require!(index == frontier_index, MerkleError::NotFrontierSubtree);
+
+        let node = self.filled_subtrees[level];
+        if node == H::zero_hash(level) {
+            Ok(H::zero_hash(level))
+        } else {
+            Ok(node)
+        }
+    }
+}
+
+/// Root of the subtree covering leaves `[index * 2^level, (index + 1) *
+/// 2^level)`, computed from a full leaf slice using the default on-chain
+/// hasher (Keccak). Thin wrapper over `get_subtree_root_with`.
+pub fn get_subtree_root(
+    leaves: &[[u8; 32]],
+    level: usize,
+    index: usize,
+) -> Result<[u8; 32]> {
+    get_subtree_root_with::<KeccakHasher>(leaves, level, index)
+}
+
+/// Root of the subtree covering leaves `[index * 2^level, (index + 1) *
+/// 2^level)`, computed from a full leaf slice with an arbitrary
+/// `TreeHasher`. Leaves past the end of the slice are treated as empty.
+pub fn get_subtree_root_with<H: TreeHasher>(
+    leaves: &[[u8; 32]],
+    level: usize,
+    index: usize,
+) -> Result<[u8; 32]> {
+    require!(level <= TREE_DEPTH, MerkleError::InvalidLeafIndex);
+
+    let max_index = 1usize << (TREE_DEPTH - level);
+    require!(index < max_index, MerkleError::InvalidSubtreeIndex);
+
+    let subtree_size = 1usize << level;
+    let start = index * subtree_size;
+
+    let mut nodes: Vec<[u8; 32]> = (start..start + subtree_size)
+        .map(|i| leaves.get(i).map(H::hash_leaf).unwrap_or_else(|| H::zero_hash(0)))
+        .collect();
+
+    while nodes.len() > 1 {
+        let mut next_level = Vec::with_capacity(nodes.len() / 2);
+        for pair in nodes.chunks(2) {
+            next_level.push(H::hash_pair(&pair[0], &pair[1]));
+        }
+        nodes = next_level;
     }
+
+    Ok(nodes[0])
 }
 
-/// Verify a Merkle proof
+/// Verify a Merkle proof using the default on-chain hasher (Keccak). Thin
+/// wrapper over `verify_merkle_proof_with`, which stable Rust can't default
+/// a free function's type parameter to (unlike the struct above).
 ///
 /// # Arguments
 /// * `leaf` - The leaf value being proven
@@ -220,7 +501,18 @@ pub fn verify_merkle_proof(
     siblings: &[[u8; 32]; TREE_DEPTH],
     root: &[u8; 32],
 ) -> bool {
-    let mut current_hash = *leaf;
+    verify_merkle_proof_with::<KeccakHasher>(leaf, leaf_index, siblings, root)
+}
+
+/// Verify a Merkle proof with an arbitrary `TreeHasher`, e.g. a Poseidon
+/// implementation used by an off-chain SNARK circuit.
+pub fn verify_merkle_proof_with<H: TreeHasher>(
+    leaf: &[u8; 32],
+    leaf_index: u64,
+    siblings: &[[u8; 32]; TREE_DEPTH],
+    root: &[u8; 32],
+) -> bool {
+    let mut current_hash = H::hash_leaf(leaf);
     let mut current_index = leaf_index;
 
     for level in 0..TREE_DEPTH {
@@ -228,9 +520,9 @@ pub fn verify_merkle_proof(
         let is_left = current_index % 2 == 0;
 
         current_hash = if is_left {
-            hash_pair(&current_hash, sibling)
+            H::hash_pair(&current_hash, sibling)
         } else {
-            hash_pair(sibling, &current_hash)
+            H::hash_pair(sibling, &current_hash)
         };
 
         current_index /= 2;
@@ -239,25 +531,34 @@ pub fn verify_merkle_proof(
     current_hash == *root
 }
 
-/// Generate a Merkle proof for a leaf
+/// Generate a Merkle proof for a leaf using the default on-chain hasher
+/// (Keccak). Thin wrapper over `generate_merkle_proof_with`.
 ///
 /// Note: This requires knowing all leaves, so it's typically done client-side.
 /// The on-chain program only needs to verify proofs, not generate them.
 pub fn generate_merkle_proof(
     leaves: &[[u8; 32]],
     leaf_index: usize,
+) -> Option<[[u8; 32]; TREE_DEPTH]> {
+    generate_merkle_proof_with::<KeccakHasher>(leaves, leaf_index)
+}
+
+/// Generate a Merkle proof for a leaf with an arbitrary `TreeHasher`.
+pub fn generate_merkle_proof_with<H: TreeHasher>(
+    leaves: &[[u8; 32]],
+    leaf_index: usize,
 ) -> Option<[[u8; 32]; TREE_DEPTH]> {
     if leaf_index >= leaves.len() {
         return None;
     }
 
     let mut proof = [[0u8; 32]; TREE_DEPTH];
-    let mut level_nodes: Vec<[u8; 32]> = leaves.to_vec();
+    let mut level_nodes: Vec<[u8; 32]> = leaves.iter().map(H::hash_leaf).collect();
 
     // Pad to power of 2
     let tree_size = 1 << TREE_DEPTH;
     while level_nodes.len() < tree_size {
-        level_nodes.push(get_zero_hash(0));
+        level_nodes.push(H::zero_hash(0));
     }
 
     let mut current_index = leaf_index;
@@ -277,7 +578,7 @@ pub fn generate_merkle_proof(
         for i in (0..level_nodes.len()).step_by(2) {
             let left = &level_nodes[i];
             let right = &level_nodes[i + 1];
-            next_level.push(hash_pair(left, right));
+            next_level.push(H::hash_pair(left, right));
         }
 
         level_nodes = next_level;
@@ -296,6 +597,197 @@ pub enum MerkleError {
     InvalidProof,
     #[msg("Invalid leaf index")]
     InvalidLeafIndex,
+    #[msg("Invalid subtree index")]
+    InvalidSubtreeIndex,
+    #[msg("Requested subtree index is not the tree's current frontier subtree at that level")]
+    NotFrontierSubtree,
+    #[msg("Nullifier's leaf slot is already occupied by a different nullifier")]
+    NullifierSlotCollision,
+}
+
+/// Sparse Merkle tree over the nullifier set, used to prove that a nullifier
+/// has *not* been spent yet (a commitment tree alone can only prove a
+/// commitment *was* inserted, never the absence of one).
+///
+/// Unlike `IncrementalMerkleTree`, which appends leaves left-to-right and
+/// tracks the rightmost frontier, a key's leaf position here is derived from
+/// the key itself: the top `TREE_DEPTH` bits of the 32-byte nullifier select
+/// one of `2^TREE_DEPTH` leaf slots. Only nodes that differ from the zero
+/// hash are stored, in a `BTreeMap` (deterministic iteration order, no
+/// hashing-based randomness, unlike `HashMap`) keyed by `(level, index)`; any
+/// node not present is implicitly `get_zero_hash(level)`. This is the same
+/// "compact sparse tree" trick merkletree-rs uses, and it produces the exact
+/// same root a fully-materialized depth-`TREE_DEPTH` tree would.
+///
+/// Note that `TREE_DEPTH` (10) bits address only 1,024 leaf slots, far fewer
+/// than the 2^256 possible nullifier values, so distinct nullifiers can and
+/// will collide into the same slot once enough nullifiers have been spent
+/// (a birthday-bound problem, likely after only a few dozen insertions).
+/// Unlike a full-width sparse tree, this can't tell two colliding keys'
+/// leaves apart by position alone, so `insert` keeps the raw key alongside
+/// each occupied slot and rejects `MerkleError::NullifierSlotCollision` if
+/// the slot is already held by a *different* key, instead of silently
+/// overwriting it (which would strand the original key's owner unable to
+/// prove either membership or non-membership of their nullifier).
+#[derive(Clone, Debug)]
+pub struct SparseMerkleTree<H: TreeHasher = KeccakHasher> {
+    nodes: std::collections::BTreeMap<(usize, u64), [u8; 32]>,
+    /// Raw key occupying each leaf slot, used only to detect a second,
+    /// distinct key mapping to the same slot; not part of the tree's hash
+    /// state.
+    keys: std::collections::BTreeMap<u64, [u8; 32]>,
+    hasher: PhantomData<H>,
+}
+
+impl<H: TreeHasher> Default for SparseMerkleTree<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sibling path from a nullifier's leaf slot up to the root, usable as either
+/// a membership proof (leaf currently holds the nullifier) or a
+/// non-membership proof (leaf still holds the zero value).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SparseMerkleProof {
+    pub siblings: [[u8; 32]; TREE_DEPTH],
+}
+
+/// Leaf slot a nullifier maps to: its top `TREE_DEPTH` bits, read big-endian.
+fn leaf_index_from_key(key: &[u8; 32]) -> u64 {
+    let top_bits = u16::from_be_bytes([key[0], key[1]]);
+    (top_bits >> (16 - TREE_DEPTH)) as u64
+}
+
+impl<H: TreeHasher> SparseMerkleTree<H> {
+    /// Create a new, empty sparse tree (root equal to the empty tree's root).
+    pub fn new() -> Self {
+        Self {
+            nodes: std::collections::BTreeMap::new(),
+            keys: std::collections::BTreeMap::new(),
+            hasher: PhantomData,
+        }
+    }
+
+    /// Node at `(level, index)`, falling back to the zero hash for that level
+    /// when the position has never been written.
+    fn node(&self, level: usize, index: u64) -> [u8; 32] {
+        self.nodes
+            .get(&(level, index))
+            .copied()
+            .unwrap_or_else(|| H::zero_hash(level))
+    }
+
+    /// Insert a nullifier, updating every node on the path from its leaf slot
+    /// to the root. Returns the leaf index it was stored at.
+    ///
+    /// Errors with `MerkleError::NullifierSlotCollision` if the slot is
+    /// already occupied by a *different* key; re-inserting the same key is a
+    /// no-op that succeeds (a nullifier can be marked spent more than once
+    /// without harm).
+    pub fn insert(&mut self, key: [u8; 32]) -> Result<u64> {
+        let leaf_index = leaf_index_from_key(&key);
+
+        if let Some(existing) = self.keys.get(&leaf_index) {
+            require!(*existing == key, MerkleError::NullifierSlotCollision);
+            return Ok(leaf_index);
+        }
+        self.keys.insert(leaf_index, key);
+
+        let mut current_hash = H::hash_leaf(&key);
+        let mut current_index = leaf_index;
+        self.nodes.insert((0, current_index), current_hash);
+
+        for level in 0..TREE_DEPTH {
+            let sibling_index = current_index ^ 1;
+            let sibling = self.node(level, sibling_index);
+            current_hash = if current_index % 2 == 0 {
+                H::hash_pair(&current_hash, &sibling)
+            } else {
+                H::hash_pair(&sibling, &current_hash)
+            };
+            current_index /= 2;
+            self.nodes.insert((level + 1, current_index), current_hash);
+        }
+
+        Ok(leaf_index)
+    }
+
+    /// Current root of the sparse tree.
+    pub fn root(&self) -> [u8; 32] {
+        self.node(TREE_DEPTH, 0)
+    }
+
+    /// Sibling path for `key`'s leaf slot, usable as a membership or
+    /// non-membership proof depending on whether the slot is currently
+    /// occupied.
+    pub fn proof(&self, key: &[u8; 32]) -> SparseMerkleProof {
+        let mut current_index = leaf_index_from_key(key);
+        let mut siblings = [[0u8; 32]; TREE_DEPTH];
+
+        for (level, sibling) in siblings.iter_mut().enumerate() {
+            let sibling_index = current_index ^ 1;
+            *sibling = self.node(level, sibling_index);
+            current_index /= 2;
+        }
+
+        SparseMerkleProof { siblings }
+    }
+}
+
+/// Verify that `key` is recorded at its slot in `root`, using the default
+/// on-chain hasher (Keccak).
+pub fn verify_membership(key: &[u8; 32], proof: &SparseMerkleProof, root: &[u8; 32]) -> bool {
+    verify_membership_with::<KeccakHasher>(key, proof, root)
+}
+
+/// Verify that `key` is recorded at its slot in `root`, with an arbitrary
+/// `TreeHasher`.
+pub fn verify_membership_with<H: TreeHasher>(
+    key: &[u8; 32],
+    proof: &SparseMerkleProof,
+    root: &[u8; 32],
+) -> bool {
+    recompute_sparse_root::<H>(H::hash_leaf(key), leaf_index_from_key(key), proof) == *root
+}
+
+/// Verify that `key`'s slot is still empty (the zero value) in `root`, using
+/// the default on-chain hasher (Keccak). This is the double-spend check: a
+/// nullifier that verifies here has not been recorded yet.
+pub fn verify_non_membership(key: &[u8; 32], proof: &SparseMerkleProof, root: &[u8; 32]) -> bool {
+    verify_non_membership_with::<KeccakHasher>(key, proof, root)
+}
+
+/// Verify that `key`'s slot is still empty in `root`, with an arbitrary
+/// `TreeHasher`.
+pub fn verify_non_membership_with<H: TreeHasher>(
+    key: &[u8; 32],
+    proof: &SparseMerkleProof,
+    root: &[u8; 32],
+) -> bool {
+    recompute_sparse_root::<H>(H::zero_hash(0), leaf_index_from_key(key), proof) == *root
+}
+
+/// Recompute the root implied by starting from `leaf_hash` at `leaf_index`
+/// and folding in `proof.siblings` level by level.
+fn recompute_sparse_root<H: TreeHasher>(
+    leaf_hash: [u8; 32],
+    leaf_index: u64,
+    proof: &SparseMerkleProof,
+) -> [u8; 32] {
+    let mut current_hash = leaf_hash;
+    let mut current_index = leaf_index;
+
+    for sibling in proof.siblings.iter() {
+        current_hash = if current_index % 2 == 0 {
+            H::hash_pair(&current_hash, sibling)
+        } else {
+            H::hash_pair(sibling, &current_hash)
+        };
+        current_index /= 2;
+    }
+
+    current_hash
 }
 
 #[cfg(test)]
@@ -309,7 +801,7 @@ mod tests {
         zeros[0] = ZERO_VALUE;
 
         for i in 1..=10 {
-            zeros[i] = hash_pair(&zeros[i - 1], &zeros[i - 1]);
+            zeros[i] = hash_intermediate(&zeros[i - 1], &zeros[i - 1]);
         }
 
         println!("\n// Precomputed ZERO_HASHES for TREE_DEPTH = 10");
@@ -326,16 +818,138 @@ mod tests {
         println!("];");
     }
 
+    #[test]
+    fn test_internal_node_cannot_be_verified_as_leaf() {
+        // An internal node one level above a pair of leaves...
+        let leaf_a = hash_leaf(&[1u8; 32]);
+        let leaf_b = hash_leaf(&[2u8; 32]);
+        let internal_node = hash_intermediate(&leaf_a, &leaf_b);
+
+        // ...must not be indistinguishable from a leaf: re-hashing it with the
+        // leaf domain tag produces something different from hashing it with
+        // the intermediate tag, so it can never stand in for a real leaf.
+        assert_ne!(hash_leaf(&internal_node), internal_node);
+
+        // Presenting that internal node as a leaf, with the genuine proof for
+        // its left child's position, does not verify against the tree's root.
+        let leaves = [[1u8; 32], [2u8; 32]];
+        let mut tree = IncrementalMerkleTree::<KeccakHasher>::new();
+        for leaf in &leaves {
+            tree.insert(*leaf).unwrap();
+        }
+        let proof = generate_merkle_proof(&leaves, 0).unwrap();
+        assert!(verify_merkle_proof(&leaves[0], 0, &proof, &tree.root()));
+        assert!(!verify_merkle_proof(&internal_node, 0, &proof, &tree.root()));
+    }
+
+    /// Stand-in for an arithmetization-friendly hasher (e.g. Poseidon): reuses
+    /// Keccak under the hood, but with its own domain tags, so this test only
+    /// proves the tree is actually generic over `H` rather than Keccak-only.
+    #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+    struct FakePoseidonHasher;
+
+    impl TreeHasher for FakePoseidonHasher {
+        const ZERO_LEAF: [u8; 32] = [0xEE; 32];
+
+        fn hash_leaf(leaf: &[u8; 32]) -> [u8; 32] {
+            let mut combined = [0u8; 33];
+            combined[0] = 0x02;
+            combined[1..].copy_from_slice(leaf);
+            keccak::hash(&combined).to_bytes()
+        }
+
+        fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+            let mut combined = [0u8; 65];
+            combined[0] = 0x03;
+            combined[1..33].copy_from_slice(left);
+            combined[33..].copy_from_slice(right);
+            keccak::hash(&combined).to_bytes()
+        }
+    }
+
+    #[test]
+    fn test_tree_is_generic_over_hasher() {
+        let mut tree = IncrementalMerkleTree::<FakePoseidonHasher>::new();
+        assert_eq!(tree.root(), FakePoseidonHasher::zero_hash(TREE_DEPTH));
+
+        tree.insert([7u8; 32]).unwrap();
+        // Inserting a leaf moves the root off this hasher's empty-tree root.
+        assert_ne!(tree.root(), FakePoseidonHasher::zero_hash(TREE_DEPTH));
+
+        // Swapping the hasher must produce a different root than Keccak would.
+        let mut keccak_tree = IncrementalMerkleTree::<KeccakHasher>::new();
+        keccak_tree.insert([7u8; 32]).unwrap();
+        assert_ne!(tree.root(), keccak_tree.root());
+    }
+
+    #[test]
+    fn test_get_subtree_root_matches_full_path_for_frontier() {
+        let leaves: Vec<[u8; 32]> = (0..4)
+            .map(|i| {
+                let mut leaf = [0u8; 32];
+                leaf[0] = i as u8;
+                leaf
+            })
+            .collect();
+
+        let mut tree = IncrementalMerkleTree::<KeccakHasher>::new();
+        for leaf in &leaves {
+            tree.insert(*leaf).unwrap();
+        }
+
+        // Level 0 of the frontier is whatever was last inserted on the left,
+        // i.e. index 2 after inserting leaves 0..4 (leaf 3 paired with it).
+        let level0 = tree.get_subtree_root(0, 2).unwrap();
+        assert_eq!(level0, get_subtree_root(&leaves, 0, 2).unwrap());
+
+        // Asking for any other in-range index at that level is rejected,
+        // not silently answered with the frontier's data.
+        assert!(tree.get_subtree_root(0, 0).is_err());
+        assert!(tree.get_subtree_root(0, 1).is_err());
+
+        // The top level always matches the current root.
+        assert_eq!(tree.get_subtree_root(TREE_DEPTH, 0).unwrap(), tree.root());
+
+        // Out-of-bounds index is rejected.
+        let max_index = 1u64 << (TREE_DEPTH - 1);
+        assert!(tree.get_subtree_root(1, max_index).is_err());
+    }
+
+    #[test]
+    fn test_get_subtree_root_from_leaf_slice() {
+        let leaves: Vec<[u8; 32]> = (0..4)
+            .map(|i| {
+                let mut leaf = [0u8; 32];
+                leaf[0] = i as u8;
+                leaf
+            })
+            .collect();
+
+        // A level-1 subtree over leaves [0, 1] should equal the hash of
+        // those two leaves combined directly.
+        let expected = hash_intermediate(&hash_leaf(&leaves[0]), &hash_leaf(&leaves[1]));
+        assert_eq!(get_subtree_root(&leaves, 1, 0).unwrap(), expected);
+
+        // An empty region (beyond the leaves we have) is all zero leaves.
+        let empty_subtree = get_subtree_root(&leaves, 1, 2).unwrap();
+        assert_eq!(
+            empty_subtree,
+            hash_intermediate(&get_zero_hash(0), &get_zero_hash(0))
+        );
+
+        assert!(get_subtree_root(&leaves, TREE_DEPTH + 1, 0).is_err());
+    }
+
     #[test]
     fn test_empty_tree_root() {
-        let tree = IncrementalMerkleTree::new();
+        let tree = IncrementalMerkleTree::<KeccakHasher>::new();
         let expected_root = get_zero_hash(TREE_DEPTH);
         assert_eq!(tree.root(), expected_root);
     }
 
     #[test]
     fn test_insert_single_leaf() {
-        let mut tree = IncrementalMerkleTree::new();
+        let mut tree = IncrementalMerkleTree::<KeccakHasher>::new();
         let leaf = [1u8; 32];
 
         let index = tree.insert(leaf).unwrap();
@@ -348,7 +962,7 @@ mod tests {
 
     #[test]
     fn test_insert_two_leaves() {
-        let mut tree = IncrementalMerkleTree::new();
+        let mut tree = IncrementalMerkleTree::<KeccakHasher>::new();
         let leaf1 = [1u8; 32];
         let leaf2 = [2u8; 32];
 
@@ -364,8 +978,8 @@ mod tests {
 
     #[test]
     fn test_deterministic_root() {
-        let mut tree1 = IncrementalMerkleTree::new();
-        let mut tree2 = IncrementalMerkleTree::new();
+        let mut tree1 = IncrementalMerkleTree::<KeccakHasher>::new();
+        let mut tree2 = IncrementalMerkleTree::<KeccakHasher>::new();
 
         let leaf = [42u8; 32];
 
@@ -375,6 +989,27 @@ mod tests {
         assert_eq!(tree1.root(), tree2.root());
     }
 
+    #[test]
+    fn test_known_root_history_window() {
+        let mut tree = IncrementalMerkleTree::<KeccakHasher>::new();
+
+        // Insert several leaves, remembering the root after the first one.
+        tree.insert([1u8; 32]).unwrap();
+        let stale_root = tree.root();
+
+        for i in 2..10u8 {
+            tree.insert([i; 32]).unwrap();
+        }
+
+        // A root from several inserts ago should still be known...
+        assert!(tree.is_known_root(&stale_root));
+        // ...while an unrelated root must not validate.
+        let unrelated_root = [0xABu8; 32];
+        assert!(!tree.is_known_root(&unrelated_root));
+        // The current root is always known.
+        assert!(tree.is_known_root(&tree.root()));
+    }
+
     #[test]
     fn test_verify_proof() {
         let leaves: Vec<[u8; 32]> = (0..4)
@@ -386,7 +1021,7 @@ mod tests {
             .collect();
 
         // Build tree
-        let mut tree = IncrementalMerkleTree::new();
+        let mut tree = IncrementalMerkleTree::<KeccakHasher>::new();
         for leaf in &leaves {
             tree.insert(*leaf).unwrap();
         }
@@ -397,4 +1032,204 @@ mod tests {
             assert!(valid);
         }
     }
+
+    #[test]
+    fn test_sparse_tree_empty_root_matches_incremental_empty_root() {
+        let sparse = SparseMerkleTree::<KeccakHasher>::new();
+        let empty = IncrementalMerkleTree::<KeccakHasher>::new();
+        assert_eq!(sparse.root(), empty.root());
+    }
+
+    #[test]
+    fn test_sparse_tree_membership_after_insert() {
+        let mut tree = SparseMerkleTree::<KeccakHasher>::new();
+        let nullifier = [7u8; 32];
+
+        tree.insert(nullifier).unwrap();
+        let proof = tree.proof(&nullifier);
+
+        assert!(verify_membership(&nullifier, &proof, &tree.root()));
+        assert!(!verify_non_membership(&nullifier, &proof, &tree.root()));
+    }
+
+    #[test]
+    fn test_sparse_tree_non_membership_before_insert() {
+        let mut tree = SparseMerkleTree::<KeccakHasher>::new();
+        let unspent_nullifier = [9u8; 32];
+        let other_nullifier = [1u8; 32];
+
+        // Unrelated insert shouldn't affect the non-membership of a nullifier
+        // whose slot it doesn't touch.
+        tree.insert(other_nullifier).unwrap();
+
+        let proof = tree.proof(&unspent_nullifier);
+        assert!(verify_non_membership(&unspent_nullifier, &proof, &tree.root()));
+        assert!(!verify_membership(&unspent_nullifier, &proof, &tree.root()));
+    }
+
+    #[test]
+    fn test_sparse_tree_proof_rejected_against_wrong_root() {
+        let mut tree = SparseMerkleTree::<KeccakHasher>::new();
+        let nullifier = [3u8; 32];
+        tree.insert(nullifier).unwrap();
+        let proof = tree.proof(&nullifier);
+
+        let wrong_root = [0xAB; 32];
+        assert!(!verify_membership(&nullifier, &proof, &wrong_root));
+    }
+
+    #[test]
+    fn test_sparse_tree_matches_full_tree_root() {
+        // Build a sparse tree with a handful of nullifiers, each landing in a
+        // distinct top-10-bit bucket, and check its root against a fully
+        // materialized tree over the same leaf positions: a raw all-zero
+        // leaf hashes to exactly `ZERO_VALUE` (how `ZERO_VALUE` itself is
+        // defined), so padding unused slots with `[0u8; 32]` makes
+        // `get_subtree_root` re-derive the same zero ladder the sparse tree
+        // falls back to.
+        let mut nullifiers = Vec::new();
+        for i in 0..4u8 {
+            let mut key = [0u8; 32];
+            key[0] = i; // distinct top bits -> distinct leaf slot
+            nullifiers.push(key);
+        }
+
+        let mut sparse = SparseMerkleTree::<KeccakHasher>::new();
+        for key in &nullifiers {
+            sparse.insert(*key).unwrap();
+        }
+
+        let mut leaves = vec![[0u8; 32]; 1 << TREE_DEPTH];
+        for key in &nullifiers {
+            leaves[leaf_index_from_key(key) as usize] = *key;
+        }
+
+        assert_eq!(sparse.root(), get_subtree_root(&leaves, TREE_DEPTH, 0).unwrap());
+    }
+
+    #[test]
+    fn test_sparse_tree_rejects_colliding_key_same_slot() {
+        let mut tree = SparseMerkleTree::<KeccakHasher>::new();
+
+        let first = [3u8; 32];
+        // Same top TREE_DEPTH bits as `first` (leaf_index_from_key only
+        // looks at key[0..2]), but a distinct key overall.
+        let mut colliding = [3u8; 32];
+        colliding[31] = 0xFF;
+        assert_eq!(leaf_index_from_key(&first), leaf_index_from_key(&colliding));
+
+        tree.insert(first).unwrap();
+        assert!(tree.insert(colliding).is_err());
+
+        // The original key's proof must still verify membership after the
+        // rejected collision, i.e. it wasn't overwritten.
+        let proof = tree.proof(&first);
+        assert!(verify_membership(&first, &proof, &tree.root()));
+    }
+
+    #[test]
+    fn test_sparse_tree_reinsert_same_key_is_noop() {
+        let mut tree = SparseMerkleTree::<KeccakHasher>::new();
+        let nullifier = [5u8; 32];
+
+        let first_index = tree.insert(nullifier).unwrap();
+        let root_after_first = tree.root();
+        let second_index = tree.insert(nullifier).unwrap();
+
+        assert_eq!(first_index, second_index);
+        assert_eq!(tree.root(), root_after_first);
+    }
+
+    /// Small deterministic LCG so the batch-size equivalence test below is
+    /// reproducible without pulling in a `rand` dependency.
+    fn next_pseudo_random(state: &mut u64) -> u64 {
+        *state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+        *state
+    }
+
+    fn leaf_from_seed(seed: u64) -> [u8; 32] {
+        let mut leaf = [0u8; 32];
+        leaf[0..8].copy_from_slice(&seed.to_le_bytes());
+        leaf
+    }
+
+    #[test]
+    fn test_insert_many_matches_loop_of_inserts_for_random_batch_sizes() {
+        let mut rng_state = 0x5EED_u64;
+        let mut leaf_seed = 0u64;
+
+        // Batch sizes chosen to straddle subtree boundaries at several
+        // levels (1, a power of two, one more than a power of two, and an
+        // arbitrary larger run).
+        let batch_sizes = [1usize, 2, 3, 4, 7, 8, 9, 16, 17, 33];
+
+        for &batch_size in &batch_sizes {
+            let mut leaves = Vec::with_capacity(batch_size);
+            for _ in 0..batch_size {
+                leaf_seed += 1;
+                leaves.push(leaf_from_seed(next_pseudo_random(&mut rng_state) ^ leaf_seed));
+            }
+
+            let mut looped = IncrementalMerkleTree::<KeccakHasher>::new();
+            for leaf in &leaves {
+                looped.insert(*leaf).unwrap();
+            }
+
+            let mut batched = IncrementalMerkleTree::<KeccakHasher>::new();
+            batched.insert_many(&leaves).unwrap();
+
+            assert_eq!(batched.next_index, looped.next_index, "batch size {batch_size}");
+            assert_eq!(
+                batched.filled_subtrees, looped.filled_subtrees,
+                "batch size {batch_size}"
+            );
+            assert_eq!(batched.current_root, looped.current_root, "batch size {batch_size}");
+        }
+    }
+
+    #[test]
+    fn test_insert_many_starting_mid_tree_matches_loop() {
+        // Exercise the "batch starts on a right child at some level" path by
+        // inserting a handful of leaves one at a time first, then following
+        // up with a batch on both trees.
+        let prefix: Vec<[u8; 32]> = (0..5u8).map(|i| [i; 32]).collect();
+        let batch: Vec<[u8; 32]> = (10..23u8).map(|i| [i; 32]).collect();
+
+        let mut looped = IncrementalMerkleTree::<KeccakHasher>::new();
+        for leaf in &prefix {
+            looped.insert(*leaf).unwrap();
+        }
+        for leaf in &batch {
+            looped.insert(*leaf).unwrap();
+        }
+
+        let mut batched = IncrementalMerkleTree::<KeccakHasher>::new();
+        for leaf in &prefix {
+            batched.insert(*leaf).unwrap();
+        }
+        batched.insert_many(&batch).unwrap();
+
+        assert_eq!(batched.next_index, looped.next_index);
+        assert_eq!(batched.filled_subtrees, looped.filled_subtrees);
+        assert_eq!(batched.current_root, looped.current_root);
+    }
+
+    #[test]
+    fn test_insert_many_rejects_batch_that_overflows_tree() {
+        let mut tree = IncrementalMerkleTree::<KeccakHasher>::new();
+        let oversized = vec![[1u8; 32]; (IncrementalMerkleTree::<KeccakHasher>::MAX_LEAVES + 1) as usize];
+        assert!(tree.insert_many(&oversized).is_err());
+    }
+
+    #[test]
+    fn test_insert_many_empty_batch_is_a_no_op() {
+        let mut tree = IncrementalMerkleTree::<KeccakHasher>::new();
+        tree.insert([1u8; 32]).unwrap();
+        let root_before = tree.root();
+
+        let returned_index = tree.insert_many(&[]).unwrap();
+
+        assert_eq!(returned_index, tree.next_index);
+        assert_eq!(tree.root(), root_before);
+    }
 }